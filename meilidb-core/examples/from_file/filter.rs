@@ -0,0 +1,457 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// A parsed `--filter` expression, ready to be evaluated against a document
+/// by resolving each leaf's attribute to its stored value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Comparison(Comparison),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub attribute: String,
+    pub operator: Operator,
+    pub value: Literal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Expr {
+    /// Parses a filter expression such as `adult = false AND (year > 2010 OR rating >= 4.5)`.
+    pub fn parse(input: &str) -> Result<Expr, ParseError> {
+        let tokens = lex(input)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let expr = parser.parse_or()?;
+        parser.expect_eof()?;
+        Ok(expr)
+    }
+
+    /// Evaluates the expression, asking `resolve` for the stored value of
+    /// each leaf's attribute. A leaf whose attribute cannot be resolved, or
+    /// whose stored value cannot be compared against its literal, is `false`.
+    pub fn evaluate(&self, resolve: &dyn Fn(&str) -> Option<Value>) -> bool {
+        match self {
+            Expr::And(a, b) => a.evaluate(resolve) && b.evaluate(resolve),
+            Expr::Or(a, b) => a.evaluate(resolve) || b.evaluate(resolve),
+            Expr::Not(expr) => !expr.evaluate(resolve),
+            Expr::Comparison(comparison) => comparison.evaluate(resolve),
+        }
+    }
+}
+
+impl Comparison {
+    fn evaluate(&self, resolve: &dyn Fn(&str) -> Option<Value>) -> bool {
+        let value = match resolve(&self.attribute) {
+            Some(value) => value,
+            None => return false,
+        };
+
+        match (&value, &self.value) {
+            (Value::Bool(a), Literal::Bool(b)) => compare(a, b, self.operator),
+            (Value::String(a), Literal::String(b)) => compare(a.as_str(), b.as_str(), self.operator),
+            (Value::Number(a), Literal::Number(b)) => {
+                match a.as_f64() {
+                    Some(a) => compare_f64(a, *b, self.operator),
+                    None => false,
+                }
+            },
+            _ => false,
+        }
+    }
+}
+
+fn compare<T: PartialEq + PartialOrd>(a: T, b: T, operator: Operator) -> bool {
+    match operator {
+        Operator::Eq => a == b,
+        Operator::Ne => a != b,
+        Operator::Lt => a < b,
+        Operator::Le => a <= b,
+        Operator::Gt => a > b,
+        Operator::Ge => a >= b,
+    }
+}
+
+fn compare_f64(a: f64, b: f64, operator: Operator) -> bool {
+    match operator {
+        Operator::Eq => a == b,
+        Operator::Ne => a != b,
+        Operator::Lt => a < b,
+        Operator::Le => a <= b,
+        Operator::Gt => a > b,
+        Operator::Ge => a >= b,
+    }
+}
+
+/// A filter string that could not be parsed, pointing at the byte position
+/// of the offending token and what was expected there instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub expected: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid filter at position {}: expected {}", self.position, self.expected)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    String(String),
+    True,
+    False,
+    And,
+    Or,
+    Not,
+    Op(Operator),
+    LParen,
+    RParen,
+}
+
+struct Spanned {
+    token: Token,
+    position: usize,
+}
+
+fn lex(input: &str) -> Result<Vec<Spanned>, ParseError> {
+    let mut tokens = Vec::new();
+    // Indexed by char, not by byte, so a multi-byte character (e.g. in an
+    // unquoted attribute name like `café`) is classified and sliced as a
+    // whole char instead of tripping over its continuation bytes.
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let len = input.len();
+    let mut i = 0;
+
+    let pos_at = |i: usize| chars.get(i).map(|&(pos, _)| pos).unwrap_or(len);
+    let char_at = |i: usize| chars.get(i).map(|&(_, c)| c);
+
+    while i < chars.len() {
+        let c = chars[i].1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start_pos = pos_at(i);
+        let token = match c {
+            '(' => { i += 1; Token::LParen },
+            ')' => { i += 1; Token::RParen },
+            '=' => { i += 1; Token::Op(Operator::Eq) },
+            '!' if char_at(i + 1) == Some('=') => { i += 2; Token::Op(Operator::Ne) },
+            '<' if char_at(i + 1) == Some('=') => { i += 2; Token::Op(Operator::Le) },
+            '<' => { i += 1; Token::Op(Operator::Lt) },
+            '>' if char_at(i + 1) == Some('=') => { i += 2; Token::Op(Operator::Ge) },
+            '>' => { i += 1; Token::Op(Operator::Gt) },
+            '"' => {
+                i += 1;
+                let string_start = i;
+                while i < chars.len() && chars[i].1 != '"' {
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(ParseError { position: start_pos, expected: "closing `\"`" });
+                }
+                let string = input[pos_at(string_start)..pos_at(i)].to_string();
+                i += 1;
+                Token::String(string)
+            },
+            c if c.is_ascii_digit() || (c == '-' && char_at(i + 1).map_or(false, |c| c.is_ascii_digit())) => {
+                i += 1;
+                while i < chars.len() && matches!(chars[i].1, '0'..='9' | '.') {
+                    i += 1;
+                }
+                let number = input[start_pos..pos_at(i)].parse().map_err(|_| ParseError { position: start_pos, expected: "number" })?;
+                Token::Number(number)
+            },
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                    i += 1;
+                }
+                match &input[start_pos..pos_at(i)] {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    ident => Token::Ident(ident.to_string()),
+                }
+            },
+            _ => return Err(ParseError { position: start_pos, expected: "`(`, `)`, an operator or an attribute name" }),
+        };
+
+        tokens.push(Spanned { token, position: start_pos });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Spanned],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|s| &s.token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.position)
+            .map(|s| s.position)
+            .unwrap_or_else(|| self.tokens.last().map_or(0, |s| s.position))
+    }
+
+    fn advance(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position).map(|s| &s.token);
+        self.position += 1;
+        token
+    }
+
+    fn expect_eof(&self) -> Result<(), ParseError> {
+        if self.position < self.tokens.len() {
+            return Err(ParseError { position: self.position(), expected: "end of filter" });
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_and()?;
+        while let Some(Token::Or) = self.peek() {
+            self.advance();
+            let rhs = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_unary()?;
+        while let Some(Token::And) = self.peek() {
+            self.advance();
+            let rhs = self.parse_unary()?;
+            expr = Expr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if let Some(Token::Not) = self.peek() {
+            self.advance();
+            let expr = self.parse_unary()?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(Token::LParen) => {
+                self.advance();
+                let expr = self.parse_or()?;
+                let position = self.position();
+                match self.advance() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(ParseError { position, expected: "`)`" }),
+                }
+            },
+            Some(Token::Ident(_)) => self.parse_comparison(),
+            _ => Err(ParseError { position: self.position(), expected: "an attribute name or `(`" }),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        let attribute = match self.advance() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => return Err(ParseError { position, expected: "an attribute name" }),
+        };
+
+        let position = self.position();
+        let operator = match self.advance() {
+            Some(Token::Op(operator)) => *operator,
+            _ => return Err(ParseError { position, expected: "a comparison operator (=, !=, <, <=, >, >=)" }),
+        };
+
+        let position = self.position();
+        let value = match self.advance() {
+            Some(Token::Number(n)) => Literal::Number(*n),
+            Some(Token::True) => Literal::Bool(true),
+            Some(Token::False) => Literal::Bool(false),
+            Some(Token::String(s)) => Literal::String(s.clone()),
+            Some(Token::Ident(s)) => Literal::String(s.clone()),
+            _ => return Err(ParseError { position, expected: "a number, a boolean or a string literal" }),
+        };
+
+        Ok(Expr::Comparison(Comparison { attribute, operator, value }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmp(attribute: &str, operator: Operator, value: Literal) -> Expr {
+        Expr::Comparison(Comparison { attribute: attribute.to_string(), operator, value })
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // `a OR b AND c` must parse as `a OR (b AND c)`, not `(a OR b) AND c`.
+        let expr = Expr::parse("a = 1 OR b = 2 AND c = 3").unwrap();
+        let expected = Expr::Or(
+            Box::new(cmp("a", Operator::Eq, Literal::Number(1.0))),
+            Box::new(Expr::And(
+                Box::new(cmp("b", Operator::Eq, Literal::Number(2.0))),
+                Box::new(cmp("c", Operator::Eq, Literal::Number(3.0))),
+            )),
+        );
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        // `NOT a = 1 AND b = 2` must parse as `(NOT a = 1) AND b = 2`.
+        let expr = Expr::parse("NOT a = 1 AND b = 2").unwrap();
+        let expected = Expr::And(
+            Box::new(Expr::Not(Box::new(cmp("a", Operator::Eq, Literal::Number(1.0))))),
+            Box::new(cmp("b", Operator::Eq, Literal::Number(2.0))),
+        );
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn parenthesized_group_overrides_precedence() {
+        // Parentheses force `(a OR b) AND c`, overriding the default OR < AND binding.
+        let expr = Expr::parse("(a = 1 OR b = 2) AND c = 3").unwrap();
+        let expected = Expr::And(
+            Box::new(Expr::Or(
+                Box::new(cmp("a", Operator::Eq, Literal::Number(1.0))),
+                Box::new(cmp("b", Operator::Eq, Literal::Number(2.0))),
+            )),
+            Box::new(cmp("c", Operator::Eq, Literal::Number(3.0))),
+        );
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn nested_parenthesized_groups() {
+        let expr = Expr::parse("NOT (a = 1 AND (b = 2 OR c = 3))").unwrap();
+        let expected = Expr::Not(Box::new(Expr::And(
+            Box::new(cmp("a", Operator::Eq, Literal::Number(1.0))),
+            Box::new(Expr::Or(
+                Box::new(cmp("b", Operator::Eq, Literal::Number(2.0))),
+                Box::new(cmp("c", Operator::Eq, Literal::Number(3.0))),
+            )),
+        )));
+        assert_eq!(expr, expected);
+    }
+
+    fn resolver(value: Value) -> impl Fn(&str) -> Option<Value> {
+        move |attribute| if attribute == "x" { Some(value.clone()) } else { None }
+    }
+
+    #[test]
+    fn number_operators() {
+        let resolve = resolver(Value::from(10));
+        assert!(cmp("x", Operator::Eq, Literal::Number(10.0)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Ne, Literal::Number(5.0)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Lt, Literal::Number(11.0)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Le, Literal::Number(10.0)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Gt, Literal::Number(9.0)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Ge, Literal::Number(10.0)).evaluate(&resolve));
+        assert!(!cmp("x", Operator::Eq, Literal::Number(11.0)).evaluate(&resolve));
+    }
+
+    #[test]
+    fn bool_operators() {
+        let resolve = resolver(Value::from(true));
+        assert!(cmp("x", Operator::Eq, Literal::Bool(true)).evaluate(&resolve));
+        assert!(cmp("x", Operator::Ne, Literal::Bool(false)).evaluate(&resolve));
+        assert!(!cmp("x", Operator::Eq, Literal::Bool(false)).evaluate(&resolve));
+    }
+
+    #[test]
+    fn string_operators() {
+        let resolve = resolver(Value::from("hello"));
+        assert!(cmp("x", Operator::Eq, Literal::String("hello".to_string())).evaluate(&resolve));
+        assert!(cmp("x", Operator::Ne, Literal::String("world".to_string())).evaluate(&resolve));
+        assert!(cmp("x", Operator::Lt, Literal::String("world".to_string())).evaluate(&resolve));
+        assert!(cmp("x", Operator::Gt, Literal::String("abc".to_string())).evaluate(&resolve));
+        assert!(!cmp("x", Operator::Eq, Literal::String("world".to_string())).evaluate(&resolve));
+    }
+
+    #[test]
+    fn mismatched_types_compare_false() {
+        let resolve = resolver(Value::from("hello"));
+        assert!(!cmp("x", Operator::Eq, Literal::Number(1.0)).evaluate(&resolve));
+    }
+
+    #[test]
+    fn unresolved_attribute_is_false() {
+        let resolve = |_: &str| None;
+        assert!(!cmp("missing", Operator::Eq, Literal::Bool(true)).evaluate(&resolve));
+    }
+
+    #[test]
+    fn parse_error_reports_position_of_unknown_operator() {
+        let err = Expr::parse("a AND 1").unwrap_err();
+        assert_eq!(err.position, 2);
+        assert_eq!(err.expected, "a comparison operator (=, !=, <, <=, >, >=)");
+    }
+
+    #[test]
+    fn parse_error_reports_position_of_missing_closing_paren() {
+        let err = Expr::parse("(a = 1").unwrap_err();
+        assert_eq!(err.position, 5);
+        assert_eq!(err.expected, "`)`");
+    }
+
+    #[test]
+    fn parse_error_reports_position_of_unterminated_string() {
+        let err = Expr::parse(r#"a = "unterminated"#).unwrap_err();
+        assert_eq!(err.position, 4);
+        assert_eq!(err.expected, "closing `\"`");
+    }
+
+    #[test]
+    fn parse_error_reports_position_of_trailing_tokens() {
+        let err = Expr::parse("a = 1 b = 2").unwrap_err();
+        assert_eq!(err.position, 6);
+        assert_eq!(err.expected, "end of filter");
+    }
+
+    #[test]
+    fn multibyte_unquoted_identifiers_do_not_panic() {
+        let expr = Expr::parse("café = 1").unwrap();
+        assert_eq!(expr, cmp("café", Operator::Eq, Literal::Number(1.0)));
+
+        let expr = Expr::parse(r#"name = "café""#).unwrap();
+        assert_eq!(expr, cmp("name", Operator::Eq, Literal::String("café".to_string())));
+    }
+}
@@ -0,0 +1,1013 @@
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::iter::FromIterator;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant, Duration};
+use std::{fs, io, sync::mpsc};
+
+use heed::RoTxn;
+use rustyline::{Editor, Config};
+use serde::{Serialize, Deserialize, Deserializer};
+use serde::de::{self, Visitor, SeqAccess};
+use serde_json::Value;
+use structopt::StructOpt;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use meilidb_core::{Highlight, Database, DocumentId, Index, MainT, UpdateResult};
+use meilidb_schema::{Schema, SchemaAttr};
+
+mod filter;
+
+const INDEX_NAME: &str = "default";
+
+/// The format of the file given to the `Index` command, guessed from
+/// the data file extension when not specified on the command line.
+#[derive(Debug, Clone, Copy)]
+enum InputFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl InputFormat {
+    fn guess_from_path(path: &Path) -> Option<InputFormat> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Some(InputFormat::Csv),
+            Some("json") => Some(InputFormat::Json),
+            Some("ndjson") | Some("jsonl") => Some(InputFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "csv" => Ok(InputFormat::Csv),
+            "json" => Ok(InputFormat::Json),
+            "ndjson" => Ok(InputFormat::Ndjson),
+            otherwise => Err(format!("unknown input format {:?}, expected csv, json or ndjson", otherwise)),
+        }
+    }
+}
+
+/// The format search results are printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Pretty,
+    Json,
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "pretty" => Ok(OutputFormat::Pretty),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            otherwise => Err(format!("unknown output format {:?}, expected pretty, json or ndjson", otherwise)),
+        }
+    }
+}
+
+/// A single search result in JSON/NDJSON output mode, built straight from
+/// the query-time char-based highlights, before the byte-range/cropping
+/// transform that only makes sense for the `pretty` terminal rendering.
+#[derive(Serialize)]
+struct JsonResult {
+    id: DocumentId,
+    document: indexmap::IndexMap<String, Value>,
+    matches: Vec<String>,
+    highlights: Vec<JsonHighlight>,
+}
+
+#[derive(Serialize)]
+struct JsonHighlight {
+    attribute: String,
+    char_index: u16,
+    char_length: u16,
+}
+
+#[derive(Debug, StructOpt)]
+struct IndexCommand {
+    /// The destination where the database must be created.
+    #[structopt(parse(from_os_str))]
+    database_path: PathBuf,
+
+    /// The file to index, either CSV, JSON or NDJSON.
+    #[structopt(parse(from_os_str))]
+    data_path: PathBuf,
+
+    /// The path to the schema.
+    #[structopt(long, parse(from_os_str))]
+    schema: PathBuf,
+
+    /// The format of the data file, guessed from its extension when omitted.
+    #[structopt(long)]
+    input_format: Option<InputFormat>,
+
+    #[structopt(long)]
+    update_group_size: Option<usize>,
+}
+
+#[derive(Debug, StructOpt)]
+struct SearchCommand {
+    /// The destination where the database must be created.
+    #[structopt(parse(from_os_str))]
+    database_path: PathBuf,
+
+    /// Timeout after which the search will return results.
+    #[structopt(long)]
+    fetch_timeout_ms: Option<u64>,
+
+    /// The number of returned results
+    #[structopt(short, long, default_value = "10")]
+    number_results: usize,
+
+    /// The number of characters before and after the first match
+    #[structopt(short = "C", long, default_value = "35")]
+    char_context: usize,
+
+    /// A boolean filter expression, e.g. `adult = false AND
+    /// (year > 2010 OR rating >= 4.5)`.
+    #[structopt(short, long)]
+    filter: Option<String>,
+
+    /// How search results are printed: `pretty` for the terminal, `json`/
+    /// `ndjson` for a machine-readable, pipeable output.
+    #[structopt(long, default_value = "pretty")]
+    output: OutputFormat,
+
+    /// Fields that must be displayed.
+    displayed_fields: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ServeCommand {
+    /// The destination where the database must be created.
+    #[structopt(parse(from_os_str))]
+    database_path: PathBuf,
+
+    /// The address and port the HTTP server listens on.
+    #[structopt(long, default_value = "127.0.0.1:8080")]
+    http_addr: String,
+
+    /// Number of worker threads handling HTTP requests concurrently.
+    #[structopt(long, default_value = "4")]
+    workers: usize,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    Index(IndexCommand),
+    Search(SearchCommand),
+    Serve(ServeCommand),
+}
+
+impl Command {
+    fn path(&self) -> &Path {
+        match self {
+            Command::Index(command) => &command.database_path,
+            Command::Search(command) => &command.database_path,
+            Command::Serve(command) => &command.database_path,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+struct Document(indexmap::IndexMap<String, Value>);
+
+/// Reads documents out of a CSV file, one per record, relying on the `csv`
+/// crate's own value-sniffing to turn numeric- and boolean-looking fields
+/// into the matching JSON types instead of forcing everything to a string.
+fn csv_documents(path: &Path) -> Result<impl Iterator<Item=Result<Document, Box<dyn Error>>>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(path)?;
+    let headers = rdr.headers()?.clone();
+
+    let iter = rdr.into_records().filter_map(move |record| {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        match record.deserialize(Some(&headers)) {
+            Ok(document) => Some(Ok(document)),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                None
+            }
+        }
+    });
+
+    Ok(iter)
+}
+
+/// Reads documents out of a newline-delimited JSON file, one JSON object per
+/// non-empty line, without ever holding the whole file in memory.
+fn ndjson_documents(path: &Path) -> Result<impl Iterator<Item=Result<Document, Box<dyn Error>>>, Box<dyn Error>> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+
+    let iter = file.lines().filter_map(|line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+
+        if line.trim().is_empty() {
+            return None
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(document) => Some(Ok(document)),
+            Err(e) => Some(Err(e.into())),
+        }
+    });
+
+    Ok(iter)
+}
+
+/// Opens a write transaction, runs `finalize` in it, commits, and folds the
+/// resulting update id into `max_update_id` — the one piece of commit
+/// semantics shared by every document source (plain iterator or `SeqAccess`),
+/// factored out so the two batching loops can't drift out of sync with it.
+fn commit_additions<W>(
+    database: &Database,
+    max_update_id: &mut Option<u64>,
+    finalize: impl FnOnce(&mut W) -> Result<u64, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>>
+{
+    let mut writer = database.env.write_txn().unwrap();
+    println!("committing update...");
+    let update_id = finalize(&mut writer)?;
+    writer.commit().unwrap();
+    *max_update_id = Some(max_update_id.map_or(update_id, |max: u64| max.max(update_id)));
+    println!("committed update {}", update_id);
+    Ok(())
+}
+
+/// Drives the batching/commit logic shared by every document source that can
+/// be expressed as a plain iterator (CSV and NDJSON).
+fn index_documents(
+    database: &Database,
+    index: &Index,
+    update_group_size: Option<usize>,
+    documents: impl IntoIterator<Item=Result<Document, Box<dyn Error>>>,
+) -> Result<Option<u64>, Box<dyn Error>>
+{
+    let mut documents = documents.into_iter().peekable();
+
+    let mut max_update_id = None;
+    let mut i = 0;
+
+    while documents.peek().is_some() {
+        let mut additions = index.documents_addition();
+
+        for document in &mut documents {
+            additions.update_document(document?);
+
+            print!("\rindexing document {}", i);
+            i += 1;
+
+            if let Some(group_size) = update_group_size {
+                if i % group_size == 0 { break }
+            }
+        }
+
+        println!();
+
+        commit_additions(database, &mut max_update_id, |writer| additions.finalize(writer).map_err(Into::into))?;
+    }
+
+    Ok(max_update_id)
+}
+
+/// A `Visitor` that drives a top-level JSON array element by element via
+/// `SeqAccess`, so a huge `[ {...}, {...}, ... ]` file is never buffered in
+/// full: each document is handed to `update_document` as soon as it is
+/// parsed, reusing the same group-size batching as `index_documents`.
+struct JsonArrayVisitor<'a> {
+    database: &'a Database,
+    index: &'a Index,
+    update_group_size: Option<usize>,
+}
+
+impl<'de, 'a> Visitor<'de> for JsonArrayVisitor<'a> {
+    type Value = Option<u64>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON array of documents")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Option<u64>, A::Error>
+    where A: SeqAccess<'de>
+    {
+        let mut max_update_id = None;
+        let mut i = 0;
+
+        loop {
+            let mut additions = self.index.documents_addition();
+            let mut any = false;
+
+            while let Some(document) = seq.next_element::<Document>()? {
+                any = true;
+                additions.update_document(document);
+
+                print!("\rindexing document {}", i);
+                i += 1;
+
+                if let Some(group_size) = self.update_group_size {
+                    if i % group_size == 0 { break }
+                }
+            }
+
+            if !any { break }
+
+            println!();
+
+            commit_additions(self.database, &mut max_update_id, |writer| additions.finalize(writer).map_err(Into::into))
+                .map_err(de::Error::custom)?;
+        }
+
+        Ok(max_update_id)
+    }
+}
+
+fn index_json_array(
+    database: &Database,
+    index: &Index,
+    update_group_size: Option<usize>,
+    reader: impl io::Read,
+) -> Result<Option<u64>, Box<dyn Error>>
+{
+    let visitor = JsonArrayVisitor { database, index, update_group_size };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let max_update_id = deserializer.deserialize_seq(visitor)?;
+    Ok(max_update_id)
+}
+
+fn index_command(command: IndexCommand, database: Database) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+
+    let (sender, receiver) = mpsc::sync_channel(100);
+    let update_fn = move |update: UpdateResult| sender.send(update.update_id).unwrap();
+    let index = match database.open_index(INDEX_NAME) {
+        Some(index) => index,
+        None => database.create_index(INDEX_NAME).unwrap()
+    };
+
+    let done = database.set_update_callback(INDEX_NAME, Box::new(update_fn));
+    assert!(done, "could not set the index update function");
+
+    let env = &database.env;
+
+    let schema = {
+        let string = fs::read_to_string(&command.schema)?;
+        toml::from_str(&string).unwrap()
+    };
+
+    let mut writer = env.write_txn().unwrap();
+    match index.main.schema(&writer)? {
+        Some(current_schema) => {
+            if current_schema != schema {
+                return Err(meilidb_core::Error::SchemaDiffer.into())
+            }
+            writer.abort();
+        },
+        None => {
+            index.schema_update(&mut writer, schema)?;
+            writer.commit().unwrap();
+        },
+    }
+
+    let format = command.input_format
+        .or_else(|| InputFormat::guess_from_path(&command.data_path))
+        .ok_or_else(|| format!(
+            "could not guess the input format of {:?}, specify one with --input-format",
+            command.data_path,
+        ))?;
+
+    let max_update_id = match format {
+        InputFormat::Csv => {
+            let documents = csv_documents(&command.data_path)?;
+            index_documents(&database, &index, command.update_group_size, documents)?
+        },
+        InputFormat::Ndjson => {
+            let documents = ndjson_documents(&command.data_path)?;
+            index_documents(&database, &index, command.update_group_size, documents)?
+        },
+        InputFormat::Json => {
+            let file = fs::File::open(&command.data_path)?;
+            index_json_array(&database, &index, command.update_group_size, io::BufReader::new(file))?
+        },
+    };
+
+    match max_update_id {
+        Some(max_update_id) => {
+            println!("Waiting for update {}", max_update_id);
+            for id in receiver {
+                if id == max_update_id { break }
+            }
+        },
+        None => println!("no document was indexed"),
+    }
+
+    println!("database created in {:.2?} at: {:?}", start.elapsed(), command.database_path);
+
+    Ok(())
+}
+
+fn display_highlights(text: &str, ranges: &[usize]) -> io::Result<()> {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut highlighted = false;
+
+    for range in ranges.windows(2) {
+        let [start, end] = match range { [start, end] => [*start, *end], _ => unreachable!() };
+        if highlighted {
+            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        }
+        write!(&mut stdout, "{}", &text[start..end])?;
+        stdout.reset()?;
+        highlighted = !highlighted;
+    }
+
+    Ok(())
+}
+
+fn char_to_byte_range(index: usize, length: usize, text: &str) -> (usize, usize) {
+    let mut byte_index = 0;
+    let mut byte_length = 0;
+
+    for (n, (i, c)) in text.char_indices().enumerate() {
+        if n == index {
+            byte_index = i;
+        }
+
+        if n + 1 == index + length {
+            byte_length = i - byte_index + c.len_utf8();
+            break;
+        }
+    }
+
+    (byte_index, byte_length)
+}
+
+fn create_highlight_areas(text: &str, highlights: &[Highlight]) -> Vec<usize> {
+    let mut byte_indexes = BTreeMap::new();
+
+    for highlight in highlights {
+        let char_index = highlight.char_index as usize;
+        let char_length = highlight.char_length as usize;
+        let (byte_index, byte_length) = char_to_byte_range(char_index, char_length, text);
+
+        match byte_indexes.entry(byte_index) {
+            Entry::Vacant(entry) => { entry.insert(byte_length); },
+            Entry::Occupied(mut entry) => {
+                if *entry.get() < byte_length {
+                    entry.insert(byte_length);
+                }
+            },
+        }
+    }
+
+    let mut title_areas = Vec::new();
+    title_areas.push(0);
+    for (byte_index, length) in byte_indexes {
+        title_areas.push(byte_index);
+        title_areas.push(byte_index + length);
+    }
+    title_areas.push(text.len());
+    title_areas.sort_unstable();
+    title_areas
+}
+
+/// note: matches must have been sorted by `char_index` and `char_length` before being passed.
+///
+/// ```no_run
+/// matches.sort_unstable_by_key(|m| (m.char_index, m.char_length));
+///
+/// let matches = matches.matches.iter().filter(|m| SchemaAttr::new(m.attribute) == attr).cloned();
+///
+/// let (text, matches) = crop_text(&text, matches, 35);
+/// ```
+fn crop_text(
+    text: &str,
+    highlights: impl IntoIterator<Item=Highlight>,
+    context: usize,
+) -> (String, Vec<Highlight>)
+{
+    let mut highlights = highlights.into_iter().peekable();
+
+    let char_index = highlights.peek().map(|m| m.char_index as usize).unwrap_or(0);
+    let start = char_index.saturating_sub(context);
+    let text = text.chars().skip(start).take(context * 2).collect();
+
+    let highlights = highlights
+        .take_while(|m| {
+            (m.char_index as usize) + (m.char_length as usize) <= start + (context * 2)
+        })
+        .map(|highlight| {
+            Highlight { char_index: highlight.char_index - start as u16, ..highlight }
+        })
+        .collect();
+
+    (text, highlights)
+}
+
+/// Fetches the schema of an already-indexed database, shared by the CLI
+/// search command and the HTTP server.
+fn open_schema(index: &Index, reader: &RoTxn<MainT>) -> Result<Schema, Box<dyn Error>> {
+    index.main.schema(reader)?.ok_or_else(|| meilidb_core::Error::SchemaMissing.into())
+}
+
+/// Builds the `with_filter` predicate for a parsed filter expression, shared
+/// by the CLI search command and the HTTP server.
+fn filter_predicate<'a>(
+    index: &'a Index,
+    reader: &'a RoTxn<MainT>,
+    schema: &'a Schema,
+    expr: &'a filter::Expr,
+) -> impl Fn(DocumentId) -> bool + 'a
+{
+    move |document_id| {
+        expr.evaluate(&|name| {
+            let attr = schema.attribute(name)?;
+            index.document_attribute::<Value>(reader, document_id, attr).unwrap()
+        })
+    }
+}
+
+/// Runs a query against `index` and returns the same structured results as
+/// the CLI's JSON output mode. This is the query entry point shared by the
+/// CLI search command's `json`/`ndjson` output modes and the `GET /search`
+/// HTTP handler; the `pretty` output mode builds and runs its own query.
+fn run_search(
+    index: &Index,
+    reader: &RoTxn<MainT>,
+    schema: &Schema,
+    query: &str,
+    filter: Option<&str>,
+    fields: Option<&HashSet<&str>>,
+    number_results: usize,
+) -> Result<Vec<JsonResult>, Box<dyn Error>>
+{
+    let expr = filter.map(filter::Expr::parse).transpose()?;
+
+    let documents = match &expr {
+        Some(expr) => {
+            let builder = index.query_builder();
+            let builder = builder.with_filter(filter_predicate(index, reader, schema, expr));
+            builder.query(reader, query, 0..number_results)?
+        },
+        None => {
+            let builder = index.query_builder();
+            builder.query(reader, query, 0..number_results)?
+        }
+    };
+
+    let mut results = Vec::with_capacity(documents.len());
+
+    for mut doc in documents {
+        doc.highlights.sort_unstable_by_key(|m| (m.char_index, m.char_length));
+
+        let document = match index.document::<Document>(reader, fields, doc.id)? {
+            Some(document) => document,
+            None => continue,
+        };
+
+        let mut matching_attributes = HashSet::new();
+        for highlight in &doc.highlights {
+            let attr = SchemaAttr::new(highlight.attribute);
+            matching_attributes.insert(schema.attribute_name(attr).to_string());
+        }
+
+        let highlights = doc.highlights.iter().map(|h| JsonHighlight {
+            attribute: schema.attribute_name(SchemaAttr::new(h.attribute)).to_string(),
+            char_index: h.char_index,
+            char_length: h.char_length,
+        }).collect();
+
+        results.push(JsonResult {
+            id: doc.id,
+            document: document.0,
+            matches: Vec::from_iter(matching_attributes),
+            highlights,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Splits a request's `?a=b&c=d` query string into decoded key/value pairs.
+fn parse_query_string(url: &str) -> BTreeMap<String, String> {
+    let query = match url.splitn(2, '?').nth(1) {
+        Some(query) => query,
+        None => return BTreeMap::new(),
+    };
+
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            (url_decode(key), url_decode(value))
+        })
+        .collect()
+}
+
+/// Percent-decodes `input` into bytes first, then validates the whole
+/// sequence as UTF-8, so a multi-byte character split across several `%XX`
+/// escapes (e.g. `%C3%A9` for `é`) decodes correctly instead of being
+/// reassembled one byte at a time into garbage `char`s.
+fn url_decode(input: &str) -> String {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '+' => bytes.push(b' '),
+            '%' => {
+                let digits = (chars.next().and_then(|c| c.to_digit(16)), chars.next().and_then(|c| c.to_digit(16)));
+                match digits {
+                    (Some(hi), Some(lo)) => bytes.push((hi * 16 + lo) as u8),
+                    _ => bytes.push(b'%'),
+                }
+            },
+            c => bytes.extend_from_slice(c.to_string().as_bytes()),
+        }
+    }
+
+    String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+fn handle_search(database: &Database, index: &Index, request: &Request) -> Result<String, Box<dyn Error>> {
+    let reader = database.env.read_txn().unwrap();
+    let schema = open_schema(index, &reader)?;
+
+    let params = parse_query_string(request.url());
+    let query = params.get("q").ok_or("missing `q` query parameter")?;
+    let limit = params.get("limit")
+        .map(|limit| limit.parse())
+        .transpose()?
+        .unwrap_or(20);
+    let filter = params.get("filter").map(String::as_str);
+
+    let results = run_search(index, &reader, &schema, query, filter, None, limit)?;
+    Ok(serde_json::to_string(&results)?)
+}
+
+fn handle_documents(
+    database: &Database,
+    index: &Index,
+    receiver: &mpsc::Receiver<u64>,
+    request: &mut Request,
+) -> Result<String, Box<dyn Error>>
+{
+    let max_update_id = index_json_array(database, index, None, request.as_reader())?;
+
+    if let Some(max_update_id) = max_update_id {
+        for id in receiver.iter() {
+            if id == max_update_id { break }
+        }
+    }
+
+    match max_update_id {
+        Some(max_update_id) => Ok(format!(r#"{{"updateId":{}}}"#, max_update_id)),
+        None => Ok(r#"{"updateId":null}"#.to_string()),
+    }
+}
+
+/// Handles one request, isolating any panic raised while doing so (e.g. a
+/// bug in the filter lexer, or a `.unwrap()` on a failed DB read) so it
+/// turns into a 500 response instead of silently killing this worker
+/// thread — a fixed-size worker pool never respawns a thread that panics,
+/// and enough dead workers leave `server.incoming_requests()` unpolled.
+fn handle_request(
+    database: &Database,
+    index: &Index,
+    receiver: &Mutex<mpsc::Receiver<u64>>,
+    mut request: Request,
+) {
+    let path = request.url().splitn(2, '?').next().unwrap_or("").to_string();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        match (request.method(), path.as_str()) {
+            (Method::Get, "/search") => handle_search(database, index, &request),
+            (Method::Post, "/documents") => {
+                let receiver = receiver.lock().unwrap();
+                handle_documents(database, index, &receiver, &mut request)
+            },
+            (method, path) => Err(format!("no route for {} {}", method, path).into()),
+        }
+    }));
+
+    let response = match result {
+        Ok(Ok(body)) => Response::from_string(body)
+            .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+            .with_status_code(200),
+        Ok(Err(e)) => Response::from_string(format!(r#"{{"error":{:?}}}"#, e.to_string()))
+            .with_status_code(400),
+        Err(_) => Response::from_string(r#"{"error":"internal error"}"#.to_string())
+            .with_status_code(500),
+    };
+
+    let _ = request.respond(response);
+}
+
+/// Serves `/search` and `/documents` off a small pool of worker threads
+/// sharing one `Server`, the standard `tiny_http` pattern, so a slow
+/// indexing request can't stall concurrent searches behind it.
+fn serve_command(command: ServeCommand, database: Database) -> Result<(), Box<dyn Error>> {
+    let (sender, receiver) = mpsc::sync_channel(100);
+    let update_fn = move |update: UpdateResult| sender.send(update.update_id).unwrap();
+    let index = match database.open_index(INDEX_NAME) {
+        Some(index) => index,
+        None => database.create_index(INDEX_NAME).unwrap(),
+    };
+
+    let done = database.set_update_callback(INDEX_NAME, Box::new(update_fn));
+    assert!(done, "could not set the index update function");
+
+    let server = Server::http(&command.http_addr)
+        .map_err(|e| format!("could not bind to {}: {}", command.http_addr, e))?;
+    println!("listening on http://{} with {} workers", command.http_addr, command.workers);
+
+    let server = Arc::new(server);
+    let database = Arc::new(database);
+    let index = Arc::new(index);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let handles: Vec<_> = (0..command.workers).map(|_| {
+        let server = Arc::clone(&server);
+        let database = Arc::clone(&database);
+        let index = Arc::clone(&index);
+        let receiver = Arc::clone(&receiver);
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(&database, &index, &receiver, request);
+            }
+        })
+    }).collect();
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    Ok(())
+}
+
+fn search_command(command: SearchCommand, database: Database) -> Result<(), Box<dyn Error>> {
+    let env = &database.env;
+    let index = database.open_index(INDEX_NAME).expect("Could not find index");
+    let reader = env.read_txn().unwrap();
+
+    let schema = open_schema(&index, &reader)?;
+
+    let fields = command.displayed_fields.iter().map(String::as_str);
+    let fields = HashSet::from_iter(fields);
+
+    let config = Config::builder().auto_add_history(true).build();
+    let mut readline = Editor::<()>::with_config(config);
+    let _ = readline.load_history("query-history.txt");
+
+    for result in readline.iter("Searching for: ") {
+        match result {
+            Ok(query) => {
+                let start_total = Instant::now();
+
+                if command.output != OutputFormat::Pretty {
+                    let results = match run_search(
+                        &index, &reader, &schema, &query,
+                        command.filter.as_deref(), Some(&fields), command.number_results,
+                    ) {
+                        Ok(results) => results,
+                        Err(e) => { eprintln!("{}", e); continue },
+                    };
+
+                    if command.output == OutputFormat::Ndjson {
+                        for result in &results {
+                            println!("{}", serde_json::to_string(result)?);
+                        }
+                    } else {
+                        println!("{}", serde_json::to_string(&results)?);
+                    }
+
+                    eprintln!("===== Found {} results in {:.2?} =====", results.len(), start_total.elapsed());
+                    continue;
+                }
+
+                let documents = match command.filter {
+                    Some(ref filter) => {
+                        let expr = match filter::Expr::parse(filter) {
+                            Ok(expr) => expr,
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                continue;
+                            }
+                        };
+
+                        let builder = index.query_builder();
+                        let builder = builder.with_filter(filter_predicate(&index, &reader, &schema, &expr));
+                        builder.query(&reader, &query, 0..command.number_results)?
+                    },
+                    None => {
+                        let builder = index.query_builder();
+                        builder.query(&reader, &query, 0..command.number_results)?
+                    }
+                };
+
+                let mut retrieve_duration = Duration::default();
+
+                let number_of_documents = documents.len();
+
+                for mut doc in documents {
+
+                    doc.highlights.sort_unstable_by_key(|m| (m.char_index, m.char_length));
+
+                    let start_retrieve = Instant::now();
+                    let result = index.document::<Document>(&reader, Some(&fields), doc.id);
+                    retrieve_duration += start_retrieve.elapsed();
+
+                    let document = match result {
+                        Ok(Some(document)) => document,
+                        Ok(None) => { eprintln!("missing document"); continue },
+                        Err(e) => { eprintln!("{}", e); continue },
+                    };
+
+                    println!("raw-id: {:?}", doc.id);
+                    for (name, value) in &document.0 {
+                        print!("{}: ", name);
+
+                        match value {
+                            Value::String(text) => {
+                                let attr = schema.attribute(name).unwrap();
+                                let highlights = doc.highlights.iter()
+                                                .filter(|m| SchemaAttr::new(m.attribute) == attr)
+                                                .cloned();
+                                let (text, highlights) = crop_text(text, highlights, command.char_context);
+                                let areas = create_highlight_areas(&text, &highlights);
+                                display_highlights(&text, &areas)?;
+                            },
+                            other => print!("{}", other),
+                        }
+                        println!();
+                    }
+
+                    let mut matching_attributes = HashSet::new();
+                    for highlight in &doc.highlights {
+                        let attr = SchemaAttr::new(highlight.attribute);
+                        let name = schema.attribute_name(attr);
+                        matching_attributes.insert(name);
+                    }
+                    let matching_attributes = Vec::from_iter(matching_attributes);
+                    println!("matching in: {:?}", matching_attributes);
+                    println!();
+                }
+
+                eprintln!("whole documents fields retrieve took {:.2?}", retrieve_duration);
+                eprintln!("===== Found {} results in {:.2?} =====", number_of_documents, start_total.elapsed());
+            },
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break
+            }
+        }
+    }
+
+    readline.save_history("query-history.txt").unwrap();
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+
+    let opt = Command::from_args();
+    let database = Database::open_or_create(opt.path())?;
+
+    match opt {
+        Command::Index(command) => index_command(command, database),
+        Command::Search(command) => search_command(command, database),
+        Command::Serve(command) => serve_command(command, database),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_decode_plain_ascii() {
+        assert_eq!(url_decode("hello"), "hello");
+    }
+
+    #[test]
+    fn url_decode_plus_as_space() {
+        assert_eq!(url_decode("hello+world"), "hello world");
+    }
+
+    #[test]
+    fn url_decode_percent_escaped_ascii() {
+        assert_eq!(url_decode("a%20b"), "a b");
+    }
+
+    #[test]
+    fn url_decode_multibyte_utf8_split_across_escapes() {
+        assert_eq!(url_decode("caf%C3%A9"), "café");
+    }
+
+    #[test]
+    fn url_decode_lone_percent_is_kept_literally() {
+        assert_eq!(url_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn parse_query_string_splits_and_decodes_pairs() {
+        let params = parse_query_string("/search?q=caf%C3%A9&filter=a+%3D+1");
+        assert_eq!(params.get("q").map(String::as_str), Some("café"));
+        assert_eq!(params.get("filter").map(String::as_str), Some("a = 1"));
+    }
+
+    #[test]
+    fn parse_query_string_without_query_is_empty() {
+        assert!(parse_query_string("/search").is_empty());
+    }
+
+    #[test]
+    fn parse_query_string_ignores_empty_pairs() {
+        let params = parse_query_string("/search?a=1&&b=2");
+        assert_eq!(params.len(), 2);
+    }
+
+    #[test]
+    fn char_to_byte_range_ascii() {
+        assert_eq!(char_to_byte_range(2, 3, "hello world"), (2, 3));
+    }
+
+    #[test]
+    fn char_to_byte_range_accounts_for_multibyte_chars() {
+        // "café " - "é" is 2 bytes, so the 1-char highlight starting at char
+        // index 3 ("é") must span 2 bytes, not 1.
+        let text = "café is nice";
+        assert_eq!(char_to_byte_range(3, 1, text), (3, 2));
+    }
+
+    fn write_temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn ndjson_documents_parses_one_document_per_line() {
+        let path = write_temp_file(
+            "from_file_main_test_ndjson_documents.ndjson",
+            "{\"id\": 1, \"name\": \"foo\"}\n\n{\"id\": 2, \"name\": \"bar\"}\n",
+        );
+        let documents: Vec<_> = ndjson_documents(&path).unwrap().collect::<Result<_, _>>().unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].0.get("id"), Some(&Value::from(1)));
+        assert_eq!(documents[1].0.get("name"), Some(&Value::from("bar")));
+    }
+
+    #[test]
+    fn ndjson_documents_skips_blank_lines() {
+        let path = write_temp_file(
+            "from_file_main_test_ndjson_documents_blank.ndjson",
+            "\n{\"id\": 1}\n\n",
+        );
+        let documents: Vec<_> = ndjson_documents(&path).unwrap().collect::<Result<_, _>>().unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(documents.len(), 1);
+    }
+
+    #[test]
+    fn csv_documents_sniffs_numeric_and_string_fields() {
+        let path = write_temp_file(
+            "from_file_main_test_csv_documents.csv",
+            "id,name\n1,foo\n2,bar\n",
+        );
+        let documents: Vec<_> = csv_documents(&path).unwrap().collect::<Result<_, _>>().unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].0.get("id"), Some(&Value::from(1)));
+        assert_eq!(documents[1].0.get("name"), Some(&Value::from("bar")));
+    }
+}
\ No newline at end of file